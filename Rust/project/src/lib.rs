@@ -12,25 +12,49 @@ use std::collections::BTreeMap;
 use std::mem;
 
 
+use serde::{Deserialize, Serialize};
 use strum_macros::EnumIter;
 use strum::IntoEnumIterator;
 use ulid::Ulid;
 
 const ENTER_KEY: &str = "Enter";
+const ESC_KEY: &str = "Escape";
+const STORAGE_KEY: &str = "todos-seed";
 
 // ------ ------
 //     Init
 // ------ ------
 
 // `init` describes what should happen when your app started.
-fn init(_: Url, _: &mut impl Orders<Msg>) -> Model {
-    Model {
+fn init(url: Url, orders: &mut impl Orders<Msg>) -> Model {
+    orders.subscribe(Msg::UrlChanged);
+
+    // The URL hash is the source of truth for the active filter once the app is running
+    // (see `Msg::UrlChanged`), but on a cold start with no hash fragment at all there's no
+    // URL-derived filter to use, so fall back to whatever was persisted last session.
+    let has_hash_fragment = not(url.hash_path().is_empty());
+    let persisted = load_persisted_data();
+
+    let filter = if has_hash_fragment {
+        Filter::from(url)
+    } else {
+        persisted.as_ref().map_or(Filter::All, |persisted| persisted.filter)
+    };
+
+    let model = Model {
         todos: BTreeMap::new(),
         new_todo_title: String::new(),
         selected_todo: None,
-        filter: Filter::All,
+        filter,
         base_url: Url::new(),
-    }.add_mock_data()
+        undo_stack: Vec::new(),
+        redo_stack: Vec::new(),
+    };
+
+    match persisted {
+        Some(persisted) => Model { todos: persisted.todos, ..model },
+        None => model.add_mock_data(),
+    }
 }
 
 // ------ ------
@@ -46,8 +70,10 @@ struct Model {
     new_todo_title: String,
     selected_todo: Option<SelectedTodo>,
     filter: Filter,
-    base_url: Url
-
+    base_url: Url,
+    // Each entry is the `Change` that undoes the action which pushed it.
+    undo_stack: Vec<Change>,
+    redo_stack: Vec<Change>,
 }
 
 
@@ -64,13 +90,41 @@ struct SelectedTodo {
 }
 
 //remember which filter is selected
-#[derive(Copy, Clone, Eq, PartialEq, EnumIter)]
+#[derive(Copy, Clone, Eq, PartialEq, EnumIter, Serialize, Deserialize)]
 enum Filter {
     All,
     Active,
     Completed,
  }
 
+impl From<Url> for Filter {
+    fn from(mut url: Url) -> Self {
+        match url.next_hash_path_part() {
+            Some("active") => Self::Active,
+            Some("completed") => Self::Completed,
+            _ => Self::All,
+        }
+    }
+}
+
+impl Filter {
+    fn to_url_hash_path(self) -> &'static str {
+        match self {
+            Self::All => "",
+            Self::Active => "active",
+            Self::Completed => "completed",
+        }
+    }
+
+    fn label(self) -> &'static str {
+        match self {
+            Self::All => "All",
+            Self::Active => "Active",
+            Self::Completed => "Completed",
+        }
+    }
+}
+
 // Data to be displayed at start
 impl Model {
     fn add_mock_data(mut self) -> Self {
@@ -104,6 +158,145 @@ impl Model {
     }
 }
 
+// ------ ------
+//  Undo / redo
+// ------ ------
+
+// Every entry is self-contained: applying it performs the change *and* returns the
+// `Change` that undoes it, so the same function drives both the undo and the redo stack.
+enum Change {
+    InsertTodo(Todo),
+    RemoveTodo(Ulid),
+    ToggleTodo(Ulid),
+    RenameTodo(Ulid, String),
+    InsertTodos(Vec<Todo>),
+    RemoveTodos(Vec<Ulid>),
+    SetCompleted(Vec<(Ulid, bool)>),
+}
+
+fn apply_change(model: &mut Model, change: Change) -> Change {
+    match change {
+        Change::InsertTodo(todo) => {
+            let id = todo.id;
+            model.todos.insert(id, todo);
+            Change::RemoveTodo(id)
+        }
+        Change::RemoveTodo(id) => {
+            let todo = model.todos.remove(&id).expect("todo to undo/redo removal of");
+            Change::InsertTodo(todo)
+        }
+        Change::ToggleTodo(id) => {
+            if let Some(todo) = model.todos.get_mut(&id) {
+                todo.completed = not(todo.completed);
+            }
+            Change::ToggleTodo(id)
+        }
+        Change::RenameTodo(id, title) => {
+            let todo = model.todos.get_mut(&id).expect("todo to undo/redo rename of");
+            let previous_title = mem::replace(&mut todo.title, title);
+            Change::RenameTodo(id, previous_title)
+        }
+        Change::InsertTodos(todos) => {
+            let ids = todos.iter().map(|todo| todo.id).collect();
+            for todo in todos {
+                model.todos.insert(todo.id, todo);
+            }
+            Change::RemoveTodos(ids)
+        }
+        Change::RemoveTodos(ids) => {
+            let removed = ids.iter().filter_map(|id| model.todos.remove(id)).collect();
+            Change::InsertTodos(removed)
+        }
+        Change::SetCompleted(states) => {
+            let previous_states = states.into_iter()
+                .filter_map(|(id, completed)| {
+                    let todo = model.todos.get_mut(&id)?;
+                    let previous_completed = mem::replace(&mut todo.completed, completed);
+                    Some((id, previous_completed))
+                })
+                .collect();
+            Change::SetCompleted(previous_states)
+        }
+    }
+}
+
+// Push the `Change` that undoes the action just performed, and forget the redo history
+// it invalidates.
+fn push_undo(model: &mut Model, inverse: Change) {
+    model.undo_stack.push(inverse);
+    model.redo_stack.clear();
+}
+
+// ------ ------
+//  Persistence
+// ------ ------
+
+// `ElRef` can't be serialized, so `SelectedTodo` stays out of the persisted snapshot entirely
+// and `Todo`'s `Ulid` is persisted as a string rather than deriving `Serialize` on `Todo` itself.
+#[derive(Serialize, Deserialize)]
+struct PersistedTodo {
+    id: String,
+    title: String,
+    completed: bool,
+}
+
+impl From<&Todo> for PersistedTodo {
+    fn from(todo: &Todo) -> Self {
+        Self {
+            id: todo.id.to_string(),
+            title: todo.title.clone(),
+            completed: todo.completed,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct PersistedData {
+    todos: Vec<PersistedTodo>,
+    filter: Filter,
+}
+
+impl From<&Model> for PersistedData {
+    fn from(model: &Model) -> Self {
+        Self {
+            todos: model.todos.values().map(PersistedTodo::from).collect(),
+            filter: model.filter,
+        }
+    }
+}
+
+struct LoadedData {
+    todos: BTreeMap<Ulid, Todo>,
+    filter: Filter,
+}
+
+fn load_persisted_data() -> Option<LoadedData> {
+    let storage = window().local_storage().ok()??;
+    let serialized = storage.get_item(STORAGE_KEY).ok()??;
+    let persisted: PersistedData = serde_json::from_str(&serialized).ok()?;
+
+    let todos = persisted.todos.into_iter()
+        .filter_map(|persisted_todo| {
+            let id = Ulid::from_string(&persisted_todo.id).ok()?;
+            Some((id, Todo {
+                id,
+                title: persisted_todo.title,
+                completed: persisted_todo.completed,
+            }))
+        })
+        .collect();
+
+    Some(LoadedData { todos, filter: persisted.filter })
+}
+
+fn save_to_local_storage(model: &Model) {
+    if let Ok(Some(storage)) = window().local_storage() {
+        if let Ok(serialized) = serde_json::to_string(&PersistedData::from(model)) {
+            storage.set_item(STORAGE_KEY, &serialized).ok();
+        }
+    }
+}
+
 // ------ ------
 //    Update
 // ------ ------
@@ -138,15 +331,21 @@ enum Msg {
     //It stores a new title to SelectedTodo
     SelectedTodoTitleChanged(String),
     //It "moves" title from SelectedTodo into the corresponding Todo in todos
-    SaveSelectedTodo
-    
+    SaveSelectedTodo,
+    //Escape was pressed while editing - discard the in-progress title
+    EditCanceled,
+
+    // ------ Undo / redo ------
+
+    Undo,
+    Redo,
 }
 
 // `update` describes how to handle each `Msg`.
-fn update(msg: Msg, model: &mut Model, _: &mut impl Orders<Msg>) {
+fn update(msg: Msg, model: &mut Model, orders: &mut impl Orders<Msg>) {
     match msg {
         Msg::UrlChanged(subs::UrlChanged(url)) => {
-            log!("UrlChanged", url);
+            model.filter = Filter::from(url);
         }
         Msg::NewTodoTitleChanged(title) => {
             model.new_todo_title = title;
@@ -165,44 +364,119 @@ fn update(msg: Msg, model: &mut Model, _: &mut impl Orders<Msg>) {
                     completed: false,
                 });
                 model.new_todo_title.clear();
+                push_undo(model, Change::RemoveTodo(id));
             }
             log!("CreateTodo");
+            save_to_local_storage(model);
         }
         Msg::ToggleTodo(id) => {
             if let Some(todo) = model.todos.get_mut(&id) {
                 todo.completed = not(todo.completed);
+                push_undo(model, Change::ToggleTodo(id));
             }
             log!("ToggleTodo");
+            save_to_local_storage(model);
         }
         Msg::RemoveTodo(id) => {
-            model.todos.remove(&id);
+            if let Some(todo) = model.todos.remove(&id) {
+                push_undo(model, Change::InsertTodo(todo));
+            }
             log!("RemoveTodo");
+            save_to_local_storage(model);
         }
-        
+
         // ------ Bulk operations ------
 
         Msg::CheckOrUncheckAll => {
+            let all_completed = model.todos.values().all(|todo| todo.completed);
+            let previous_states = model.todos.values()
+                .map(|todo| (todo.id, todo.completed))
+                .collect();
+            for todo in model.todos.values_mut() {
+                todo.completed = not(all_completed);
+            }
+            push_undo(model, Change::SetCompleted(previous_states));
             log!("CheckOrUncheckAll");
+            save_to_local_storage(model);
         }
         Msg::ClearCompleted => {
-            //Todo: Refractor with 'BTreeMap::drain_filter' once stable.
-            model.todos = mem::take(&mut model.todos)
+            let (completed, active): (BTreeMap<_, _>, BTreeMap<_, _>) = mem::take(&mut model.todos)
                 .into_iter()
-                .filter(|(_, todo) | not(todo.completed))
-                .collect();
+                .partition(|(_, todo)| todo.completed);
+            model.todos = active;
+
+            let removed: Vec<Todo> = completed.into_values().collect();
+            if not(removed.is_empty()) {
+                push_undo(model, Change::InsertTodos(removed));
+            }
             log!("ClearCompleted");
+            save_to_local_storage(model);
         }
-        
+
         // ------ Selection ------
 
-        Msg::SelectTodo(opt_id) => {
-            log!("SelectTodo", opt_id);
+        Msg::SelectTodo(None) => {
+            model.selected_todo = None;
+        },
+        Msg::SelectTodo(Some(id)) => {
+            if let Some(todo) = model.todos.get(&id) {
+                model.selected_todo = Some(SelectedTodo {
+                    id,
+                    title: todo.title.clone(),
+                    input_element: ElRef::default(),
+                });
+
+                let input_element = model.selected_todo.as_ref().unwrap().input_element.clone();
+                orders.after_next_render(move |_| {
+                    // A later `SelectTodo` may have already replaced `selected_todo` with a
+                    // fresh `ElRef` before this render fires, leaving this one never bound to
+                    // a DOM node - just skip focusing instead of unwrapping a `None`.
+                    if let Some(elem) = input_element.get() {
+                        let _ = elem.focus();
+                    }
+                    None
+                });
+            }
         },
         Msg::SelectedTodoTitleChanged(title) => {
-            log!("SelectedTodoTitleChanged", title);
+            if let Some(selected_todo) = &mut model.selected_todo {
+                selected_todo.title = title;
+            }
         },
         Msg::SaveSelectedTodo => {
+            if let Some(selected_todo) = model.selected_todo.take() {
+                let title = selected_todo.title.trim();
+                if title.is_empty() {
+                    if let Some(todo) = model.todos.remove(&selected_todo.id) {
+                        push_undo(model, Change::InsertTodo(todo));
+                    }
+                } else if let Some(todo) = model.todos.get_mut(&selected_todo.id) {
+                    let previous_title = mem::replace(&mut todo.title, title.to_owned());
+                    push_undo(model, Change::RenameTodo(selected_todo.id, previous_title));
+                }
+            }
             log!("SaveSelectedTodo");
+            save_to_local_storage(model);
+        }
+        Msg::EditCanceled => {
+            model.selected_todo = None;
+        }
+
+        // ------ Undo / redo ------
+
+        Msg::Undo => {
+            if let Some(change) = model.undo_stack.pop() {
+                let forward_change = apply_change(model, change);
+                model.redo_stack.push(forward_change);
+                save_to_local_storage(model);
+            }
+        }
+        Msg::Redo => {
+            if let Some(change) = model.redo_stack.pop() {
+                let inverse_change = apply_change(model, change);
+                model.undo_stack.push(inverse_change);
+                save_to_local_storage(model);
+            }
         }
     }
 }
@@ -213,14 +487,22 @@ fn update(msg: Msg, model: &mut Model, _: &mut impl Orders<Msg>) {
 
 // `view` describes what to display.
 fn view(model: &Model) -> Vec<Node<Msg>> {
+    // The footer (and its Undo/Redo buttons) also has to stay visible right after
+    // "Clear completed" empties the list, or there'd be no way to undo it - but `view_main`
+    // (the toggle-all checkbox and the todo `<ul>`) stays gated on there being todos to show.
+    let has_history = not(model.undo_stack.is_empty()) || not(model.redo_stack.is_empty());
+
     nodes![
         view_header(&model.new_todo_title),
-        IF!(not(model.todos.is_empty()) => vec![
-            // This section should be hidden by default and shown when there are todos
-            view_main(&model.todos, model.selected_todo.as_ref()),
-            // This footer should be hidden by default and shown when there are todos
-            view_footer(&model.todos, model.filter),
-        ]),
+        // This section should be hidden by default and shown when there are todos
+        IF!(not(model.todos.is_empty()) => view_main(&model.todos, model.selected_todo.as_ref(), model.filter)),
+        // This footer is hidden by default and shown when there are todos or undo/redo history
+        IF!(not(model.todos.is_empty()) || has_history => view_footer(
+            &model.todos,
+            model.filter,
+            not(model.undo_stack.is_empty()),
+            not(model.redo_stack.is_empty()),
+        )),
     ]
 }
 
@@ -244,30 +526,35 @@ fn view_header(new_todo_title: &str) -> Node<Msg> {
 
 // ------ main ------
 
-fn view_main(todos: &BTreeMap<Ulid, Todo>, selected_todo: Option<&SelectedTodo>) -> Node<Msg> {
+fn view_main(todos: &BTreeMap<Ulid, Todo>, selected_todo: Option<&SelectedTodo>, filter: Filter) -> Node<Msg> {
     section![C!["main"],
         view_toggle_all(todos),
-        view_todo_list(todos, selected_todo),
+        view_todo_list(todos, selected_todo, filter),
     ]
 }
 
 fn view_toggle_all(todos: &BTreeMap<Ulid, Todo>) -> Vec<Node<Msg>> {
     let all_completed = todos.values().all(|todo| todo.completed);
     vec![
-        input![C!["toggle-all"], 
+        input![C!["toggle-all"],
             attrs!{
-                At::Id => "toggle-all", 
-                At::Type => "checkbox", 
+                At::Id => "toggle-all",
+                At::Type => "checkbox",
                 At::Checked => all_completed.as_at_value()
-            }
+            },
+            ev(Ev::Change, |_| Msg::CheckOrUncheckAll),
         ],
         label![attrs!{At::For => "toggle-all"}, "Mark all as complete"],
     ]
 }
 
-fn view_todo_list(todos: &BTreeMap<Ulid, Todo>, selected_todo: Option<&SelectedTodo>) -> Node<Msg> {
+fn view_todo_list(todos: &BTreeMap<Ulid, Todo>, selected_todo: Option<&SelectedTodo>, filter: Filter) -> Node<Msg> {
     ul![C!["todo-list"],
-        todos.values().map(|todo| {
+        todos.values().filter(|todo| match filter {
+            Filter::All => true,
+            Filter::Active => not(todo.completed),
+            Filter::Completed => todo.completed,
+        }).map(|todo| {
             let id = todo.id;
             let is_selected = Some(todo.id) == selected_todo.map(|selected_todo| selected_todo.id);
 
@@ -280,7 +567,10 @@ fn view_todo_list(todos: &BTreeMap<Ulid, Todo>, selected_todo: Option<&SelectedT
                     attrs!{At::Type => "checkbox", At::Checked => todo.completed.as_at_value()},
                     ev(Ev::Change, move |_| Msg::ToggleTodo(id)),
                 ],
-                    label![&todo.title],
+                    label![
+                        &todo.title,
+                        ev(Ev::DblClick, move |_| Msg::SelectTodo(Some(id))),
+                    ],
                     button![C!["destroy"],
                         ev(Ev::Click, move |_| Msg::RemoveTodo(id))
                         ],
@@ -288,8 +578,17 @@ fn view_todo_list(todos: &BTreeMap<Ulid, Todo>, selected_todo: Option<&SelectedT
                 IF!(is_selected => {
                     let selected_todo = selected_todo.unwrap();
                     input![C!["edit"],
-                    el_ref(&selected_todo.input_element), 
-                    attrs!{At::Value => selected_todo.title},
+                        el_ref(&selected_todo.input_element),
+                        attrs!{At::Value => selected_todo.title},
+                        input_ev(Ev::Input, Msg::SelectedTodoTitleChanged),
+                        ev(Ev::Blur, |_| Msg::SaveSelectedTodo),
+                        keyboard_ev(Ev::KeyDown, |keyboard_event| {
+                            Some(match keyboard_event.key().as_str() {
+                                ENTER_KEY => Msg::SaveSelectedTodo,
+                                ESC_KEY => Msg::EditCanceled,
+                                _ => return None,
+                            })
+                        }),
                     ]
                 }),
             ]
@@ -299,18 +598,48 @@ fn view_todo_list(todos: &BTreeMap<Ulid, Todo>, selected_todo: Option<&SelectedT
 
 // ------ footer ------
 
-fn view_footer(todos: &BTreeMap<Ulid, Todo>, selected_filter: Filter) -> Node<Msg> {
+fn view_footer(todos: &BTreeMap<Ulid, Todo>, selected_filter: Filter, can_undo: bool, can_redo: bool) -> Node<Msg> {
     let completed_count = todos.values().filter(|todo| todo.completed).count();
     let active_count = todos.len() - completed_count;
 
     footer![C!["footer"],
-    // This should be `0 items left` by default
-    span![C!["todo-count"],
-    strong![active_count],
-    format!(" item{} left", if active_count == 1 { "" } else { "s" }),
+        // This should be `0 items left` by default
+        span![C!["todo-count"],
+            strong![active_count],
+            format!(" item{} left", if active_count == 1 { "" } else { "s" }),
+        ],
+        view_filters(selected_filter),
+        view_undo_redo(can_undo, can_redo),
     ]
+}
+
+fn view_undo_redo(can_undo: bool, can_redo: bool) -> Node<Msg> {
+    div![C!["undo-redo"],
+        button![C!["undo"],
+            attrs!{At::Disabled => (not(can_undo)).as_at_value()},
+            ev(Ev::Click, |_| Msg::Undo),
+            "Undo",
+        ],
+        button![C!["redo"],
+            attrs!{At::Disabled => (not(can_redo)).as_at_value()},
+            ev(Ev::Click, |_| Msg::Redo),
+            "Redo",
+        ],
     ]
+}
 
+fn view_filters(selected_filter: Filter) -> Node<Msg> {
+    ul![C!["filters"],
+        Filter::iter().map(|filter| {
+            let is_selected = filter == selected_filter;
+            li![
+                a![C![IF!(is_selected => "selected")],
+                    attrs!{At::Href => format!("#/{}", filter.to_url_hash_path())},
+                    filter.label(),
+                ]
+            ]
+        })
+    ]
 }
 
 